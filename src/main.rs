@@ -5,16 +5,19 @@ use std::{
     cmp::Ordering,
     collections::BinaryHeap,
     fs::{self, File},
-    io::{self, BufRead, BufReader, BufWriter, Write},
+    io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write},
     path::PathBuf,
+    sync::{mpsc, Arc},
+    thread,
 };
 
 /// Deduplicate huge text/binary files using external sort
 #[derive(Parser, Debug)]
 struct Args {
-    /// Input file
-    #[arg(short, long)]
-    input: PathBuf,
+    /// Input file(s). May be given more than once; the combined, deduplicated contents of all
+    /// inputs are written to `output`.
+    #[arg(short, long, required = true)]
+    input: Vec<PathBuf>,
 
     /// Output file
     #[arg(short, long)]
@@ -24,135 +27,715 @@ struct Args {
     #[arg(short, long, default_value = "./chunks")]
     temp_dir: PathBuf,
 
-    /// Max lines per chunk
-    #[arg(short, long, default_value_t = 1_000_000)]
+    /// Size in bytes of each block read from the input before it is sorted and flushed to a chunk file
+    #[arg(short, long, default_value_t = 64 * 1024 * 1024, value_parser = parse_chunk_size)]
     chunk_size: usize,
+
+    /// Field delimiter used to split lines into key columns
+    #[arg(long, default_value = "\t")]
+    delimiter: String,
+
+    /// Sort/dedup key: COLUMN[-COLUMN][:MODS], 1-based columns, MODS any of n (numeric),
+    /// r (reverse), f (case-fold). May be repeated; with none given, the whole line is the key.
+    #[arg(short = 'k', long = "key", value_parser = parse_sort_key)]
+    keys: Vec<SortKeySpec>,
+
+    /// Compress temporary chunk files with zstd at the given level (1-22); omit to disable
+    #[arg(long, value_name = "LEVEL")]
+    compress: Option<i32>,
+
+    /// Split the input into about this many newline-aligned byte ranges and sort them
+    /// concurrently with rayon, instead of reading the input sequentially. Values of 0 or 1, or
+    /// an input other than the last that does not end in a newline, use sequential reading.
+    #[arg(short = 'j', long)]
+    jobs: Option<usize>,
+
+    /// If the output path also names one of the input files, copy it aside into `temp_dir`
+    /// before reading so the tool can safely run in place over a set of files that includes
+    /// the target
+    #[arg(long)]
+    guard_output_overlap: bool,
 }
 
 /// Represents a line read as raw bytes
 type Line = Vec<u8>;
 
+/// A line's location within a read block, as a byte offset range
+type LineRange = (usize, usize);
+
+/// One `--key` specification: an inclusive, 1-based column range plus comparison modifiers
+#[derive(Debug, Clone)]
+struct SortKeySpec {
+    start: usize,
+    end: usize,
+    numeric: bool,
+    reverse: bool,
+    fold_case: bool,
+}
+
+fn parse_chunk_size(s: &str) -> Result<usize, String> {
+    let size: usize = s.parse().map_err(|_| format!("invalid chunk size: {s}"))?;
+    if size == 0 {
+        return Err("chunk size must be greater than 0".to_string());
+    }
+    Ok(size)
+}
+
+fn parse_sort_key(s: &str) -> Result<SortKeySpec, String> {
+    let (range_part, mod_part) = s.split_once(':').unwrap_or((s, ""));
+    let (start_str, end_str) = range_part.split_once('-').unwrap_or((range_part, range_part));
+
+    let start: usize = start_str
+        .parse()
+        .map_err(|_| format!("invalid key start column: {start_str}"))?;
+    let end: usize = end_str
+        .parse()
+        .map_err(|_| format!("invalid key end column: {end_str}"))?;
+    if start == 0 || end == 0 {
+        return Err("key columns are 1-based; 0 is not a valid column".to_string());
+    }
+    if end < start {
+        return Err(format!("key end column {end} is before start column {start}"));
+    }
+
+    let mut spec = SortKeySpec { start, end, numeric: false, reverse: false, fold_case: false };
+    for c in mod_part.chars() {
+        match c {
+            'n' => spec.numeric = true,
+            'r' => spec.reverse = true,
+            'f' => spec.fold_case = true,
+            other => return Err(format!("unknown key modifier '{other}'")),
+        }
+    }
+    Ok(spec)
+}
+
+/// Key-extraction and comparison configuration shared by the sort and dedup passes
+#[derive(Debug, Clone, Default)]
+struct SortConfig {
+    delimiter: u8,
+    keys: Vec<SortKeySpec>,
+}
+
+impl SortConfig {
+    fn from_args(args: &Args) -> Self {
+        SortConfig {
+            delimiter: args.delimiter.as_bytes().first().copied().unwrap_or(b'\t'),
+            keys: args.keys.clone(),
+        }
+    }
+
+    /// Compare two lines: by their configured key field(s) in order, or by the whole line if
+    /// no keys were configured.
+    fn compare(&self, a: &[u8], b: &[u8]) -> Ordering {
+        if self.keys.is_empty() {
+            return a.cmp(b);
+        }
+
+        // Lines carry their trailing line ending (see `collect_line_ranges`), but a key
+        // selecting the last column must not, or the final record of a stream whose last input
+        // file lacks a trailing newline would get a different key than an identical duplicate
+        // elsewhere.
+        let a = strip_line_ending(a);
+        let b = strip_line_ending(b);
+
+        let a_fields = field_ranges(a, self.delimiter);
+        let b_fields = field_ranges(b, self.delimiter);
+
+        for key in &self.keys {
+            let a_key = extract_key(a, &a_fields, key);
+            let b_key = extract_key(b, &b_fields, key);
+
+            let mut ord = if key.numeric {
+                parse_numeric(a_key).partial_cmp(&parse_numeric(b_key)).unwrap_or(Ordering::Equal)
+            } else if key.fold_case {
+                a_key.to_ascii_lowercase().cmp(&b_key.to_ascii_lowercase())
+            } else {
+                a_key.cmp(b_key)
+            };
+            if key.reverse {
+                ord = ord.reverse();
+            }
+            if ord != Ordering::Equal {
+                return ord;
+            }
+        }
+        Ordering::Equal
+    }
+}
+
+/// Strip a trailing `\n` or `\r\n` from `line`, if present
+fn strip_line_ending(line: &[u8]) -> &[u8] {
+    match line.strip_suffix(b"\n") {
+        Some(line) => line.strip_suffix(b"\r").unwrap_or(line),
+        None => line,
+    }
+}
+
+/// Split a line into `(start, end)` byte ranges for each delimiter-separated field
+fn field_ranges(line: &[u8], delimiter: u8) -> Vec<(usize, usize)> {
+    let mut fields = Vec::new();
+    let mut start = 0;
+    for (i, &b) in line.iter().enumerate() {
+        if b == delimiter {
+            fields.push((start, i));
+            start = i + 1;
+        }
+    }
+    fields.push((start, line.len()));
+    fields
+}
+
+/// Slice out the portion of `line` spanned by a key spec's column range, given that line's
+/// field boundaries. A start column past the last field yields an empty slice.
+fn extract_key<'a>(line: &'a [u8], fields: &[(usize, usize)], key: &SortKeySpec) -> &'a [u8] {
+    let start_idx = key.start - 1;
+    if start_idx >= fields.len() {
+        return &line[line.len()..];
+    }
+    let end_idx = (key.end - 1).min(fields.len() - 1);
+    let (start, _) = fields[start_idx];
+    let (_, end) = fields[end_idx];
+    &line[start..end]
+}
+
+fn parse_numeric(field: &[u8]) -> f64 {
+    std::str::from_utf8(field)
+        .ok()
+        .and_then(|s| s.trim().parse::<f64>().ok())
+        .unwrap_or(0.0)
+}
+
+/// If `--guard-output-overlap` is set and `output` canonicalizes to the same file as one or
+/// more entries in `inputs`, copy `output` aside into `temp_dir` and repoint those entries at
+/// the copy, returning the staged path so the caller can remove it once done. With today's
+/// two-phase split-then-merge design every input is fully drained into chunk files before
+/// `output` is ever opened for writing, so the hazard this guards against can't occur yet — but
+/// that's a property of `main`'s current shape, not a documented guarantee, so the flag stays
+/// available as a cheap, explicit safety net for callers who'd rather not depend on it.
+fn stage_output_overlap(output: &PathBuf, inputs: &mut [PathBuf], temp_dir: &PathBuf) -> io::Result<Option<PathBuf>> {
+    let Ok(canonical_output) = output.canonicalize() else {
+        // Output doesn't exist yet, so it can't overlap with any input.
+        return Ok(None);
+    };
+
+    let mut staged = None;
+    for input in inputs.iter_mut() {
+        if input.canonicalize().ok().as_deref() == Some(canonical_output.as_path()) {
+            let staged_path = staged
+                .get_or_insert_with(|| temp_dir.join(format!("output_overlap_{}.tmp", uuid::Uuid::new_v4())))
+                .clone();
+            if !staged_path.exists() {
+                fs::copy(&canonical_output, &staged_path)?;
+            }
+            *input = staged_path.clone();
+            staged = Some(staged_path);
+        }
+    }
+    Ok(staged)
+}
+
 fn main() -> io::Result<()> {
-    let args = Args::parse();
+    let mut args = Args::parse();
+    let config = Arc::new(SortConfig::from_args(&args));
 
     fs::create_dir_all(&args.temp_dir)?;
 
-    // Phase 1: Split into sorted chunks
-    let chunk_files = split_into_chunks(&args)?;
+    let staged_input = if args.guard_output_overlap {
+        stage_output_overlap(&args.output, &mut args.input, &args.temp_dir)?
+    } else {
+        None
+    };
+
+    // Phase 1: Split into sorted chunks. This fully reads every input and flushes complete,
+    // sorted chunk files to `temp_dir` before phase 2 ever touches `output`.
+    let chunk_files = match args.jobs {
+        Some(jobs) if jobs > 1 && !needs_sequential_for_boundaries(&args.input)? => {
+            parallel_split_into_chunks(&args, &config, jobs)?
+        }
+        Some(jobs) if jobs > 1 => {
+            eprintln!(
+                "warning: a non-final --input file doesn't end in a newline, so --jobs would glue lines \
+                 differently than the sequential path; falling back to sequential splitting"
+            );
+            split_into_chunks(&args, &config)?
+        }
+        _ => split_into_chunks(&args, &config)?,
+    };
     // Phase 2: Merge chunks into final deduped file
-    merge_chunks(chunk_files, &args.output)?;
+    merge_chunks(chunk_files, &args, &config)?;
+
+    if let Some(path) = staged_input {
+        let _ = fs::remove_file(path);
+    }
 
     Ok(())
 }
 
-/// Read the input file in chunks, sort, and write temp files
-fn split_into_chunks(args: &Args) -> io::Result<Vec<PathBuf>> {
-    let mut reader = BufReader::new(File::open(&args.input)?);
-    let mut buffer = Vec::with_capacity(args.chunk_size);
+/// Read all input files, back to back as one combined stream, in large blocks of `chunk_size`
+/// bytes (grown when a single line does not fit), sort each block's lines in place, and write
+/// one temp file per block. Lines are tracked as offset ranges into the block buffer rather
+/// than as owned per-line allocations. A partial line at the end of one input file is
+/// carried over and completed with bytes from the next, exactly as if the files were
+/// concatenated first.
+fn split_into_chunks(args: &Args, config: &SortConfig) -> io::Result<Vec<PathBuf>> {
+    let mut inputs = args.input.iter();
+    let mut reader: Option<BufReader<File>> = None;
+    let mut block = vec![0u8; args.chunk_size];
     let mut chunk_files = Vec::new();
-    let mut line = Vec::new();
+    // Bytes already valid at the front of `block` (a partial line carried over from the
+    // previous iteration, possibly from the previous input file), followed by freshly read bytes.
+    let mut filled = 0;
 
     let pb = ProgressBar::new_spinner();
     pb.set_style(
-        ProgressStyle::with_template("{spinner} Processed lines to chunks... {pos} processed").unwrap(),
+        ProgressStyle::with_template("{spinner} Processed bytes to chunks... {pos} processed").unwrap(),
     );
 
-    while reader.read_until(b'\n', &mut line)? > 0 {
-        buffer.push(line.clone());
-        line.clear();
-        pb.inc(1);
-
-        if buffer.len() >= args.chunk_size {
-            let file = write_sorted_chunk(&args.temp_dir, &mut buffer)?;
-            chunk_files.push(file);
+    loop {
+        let current = match &mut reader {
+            Some(r) => r,
+            None => match inputs.next() {
+                Some(path) => reader.insert(BufReader::new(File::open(path)?)),
+                None => {
+                    // No more input files: end of the combined stream.
+                    if filled > 0 {
+                        let mut ranges = collect_line_ranges(&block[..filled]);
+                        let file = write_sorted_chunk(&args.temp_dir, &block[..filled], &mut ranges, config, args.compress)?;
+                        chunk_files.push(file);
+                    }
+                    break;
+                }
+            },
+        };
+
+        let n = current.read(&mut block[filled..])?;
+        let end = filled + n;
+
+        if n == 0 {
+            // This input file is exhausted; move on to the next one without losing the bytes
+            // already carried over in `block`.
+            reader = None;
+            continue;
         }
-    }
 
-    if !buffer.is_empty() {
-        let file = write_sorted_chunk(&args.temp_dir, &mut buffer)?;
+        pb.inc(n as u64);
+
+        let split_at = match block[..end].iter().rposition(|&b| b == b'\n') {
+            Some(pos) => pos + 1,
+            None => {
+                // No newline in the block yet. If the block is full, a single line is larger
+                // than our block size, so grow it and keep reading into the extra space.
+                if end == block.len() {
+                    block.resize(block.len() * 2, 0);
+                }
+                filled = end;
+                continue;
+            }
+        };
+
+        let mut ranges = collect_line_ranges(&block[..split_at]);
+        let file = write_sorted_chunk(&args.temp_dir, &block[..split_at], &mut ranges, config, args.compress)?;
         chunk_files.push(file);
+
+        // Carry the trailing partial line to the front of the block before refilling.
+        let carry_over = end - split_at;
+        block.copy_within(split_at..end, 0);
+        filled = carry_over;
     }
 
     pb.finish_with_message("✅ Chunking complete");
     Ok(chunk_files)
 }
 
-/// Sort a buffer of raw lines and write to a tempfile
-fn write_sorted_chunk(temp_dir: &PathBuf, buffer: &mut Vec<Line>) -> io::Result<PathBuf> {
-    buffer.par_sort_unstable_by(|a, b| a.cmp(b));
+/// Check whether `input` ends with a `\n`. An empty file trivially counts as ending cleanly,
+/// since it contributes no partial line to glue onto whatever follows it.
+fn file_ends_with_newline(input: &PathBuf) -> io::Result<bool> {
+    let mut file = File::open(input)?;
+    let len = file.seek(SeekFrom::End(0))?;
+    if len == 0 {
+        return Ok(true);
+    }
+    file.seek(SeekFrom::Start(len - 1))?;
+    let mut byte = [0u8; 1];
+    file.read_exact(&mut byte)?;
+    Ok(byte[0] == b'\n')
+}
 
-    let file_path = temp_dir.join(format!("chunk_{}.tmp", uuid::Uuid::new_v4()));
-    let mut file = BufWriter::new(File::create(&file_path)?);
+/// `parallel_split_into_chunks` computes byte ranges per file and never glues a trailing partial
+/// line in one file onto the next file's first line the way `split_into_chunks` does, so the two
+/// paths would silently disagree whenever a non-final input doesn't end in a newline. Check for
+/// that case up front so the caller can fall back to the sequential path instead.
+fn needs_sequential_for_boundaries(inputs: &[PathBuf]) -> io::Result<bool> {
+    for input in inputs.iter().rev().skip(1) {
+        if !file_ends_with_newline(input)? {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Divide each input file into newline-aligned byte ranges, proportioned to its share of the
+/// total input size so that roughly `jobs` ranges are produced overall (each non-empty file
+/// gets at least one, and ranges that collapse when snapped to a newline are dropped), and
+/// sort each range into its
+/// own chunk file concurrently with rayon. This parallelizes both the read and the initial sort
+/// across cores. The caller is responsible for ensuring every input but the last ends in a
+/// newline (see `needs_sequential_for_boundaries`), since ranges never cross a file boundary.
+fn parallel_split_into_chunks(args: &Args, config: &SortConfig, jobs: usize) -> io::Result<Vec<PathBuf>> {
+    let file_lens: Vec<u64> = args.input.iter().map(|p| fs::metadata(p).map(|m| m.len())).collect::<io::Result<_>>()?;
+    let total_len: u64 = file_lens.iter().sum();
+    if total_len == 0 {
+        return Ok(Vec::new());
+    }
+
+    let mut byte_ranges: Vec<(&PathBuf, u64, u64)> = Vec::new();
+    for (path, &len) in args.input.iter().zip(&file_lens) {
+        if len == 0 {
+            continue;
+        }
+        let file_jobs = ((jobs as u64 * len / total_len).max(1)) as usize;
+        for (start, end) in newline_aligned_ranges(path, len, file_jobs)? {
+            byte_ranges.push((path, start, end));
+        }
+    }
+
+    byte_ranges
+        .into_par_iter()
+        .map(|(path, start, end)| sort_byte_range_to_chunk(args, config, path, start, end))
+        .collect()
+}
+
+/// Divide `[0, file_len)` into up to `jobs` ranges, snapping each interior boundary forward to
+/// the next `\n` so no line is split across two ranges
+fn newline_aligned_ranges(input: &PathBuf, file_len: u64, jobs: usize) -> io::Result<Vec<(u64, u64)>> {
+    let jobs = jobs.max(1) as u64;
+    let approx = file_len / jobs;
+
+    let mut boundaries = vec![0u64];
+    for i in 1..jobs {
+        let target = (approx * i).min(file_len);
+        boundaries.push(snap_to_next_newline(input, target, file_len)?);
+    }
+    boundaries.push(file_len);
+    boundaries.dedup();
+
+    Ok(boundaries.windows(2).map(|w| (w[0], w[1])).filter(|&(start, end)| start < end).collect())
+}
+
+/// Scan forward from `pos` to the end of the line it falls in, returning the offset just past
+/// the next `\n` (or `file_len` if there is none)
+fn snap_to_next_newline(input: &PathBuf, pos: u64, file_len: u64) -> io::Result<u64> {
+    if pos == 0 || pos >= file_len {
+        return Ok(pos.min(file_len));
+    }
+
+    let mut file = File::open(input)?;
+    file.seek(SeekFrom::Start(pos))?;
+    let mut reader = BufReader::new(file);
+    let mut byte = [0u8; 1];
+    let mut cur = pos;
+
+    while cur < file_len {
+        if reader.read(&mut byte)? == 0 {
+            break;
+        }
+        cur += 1;
+        if byte[0] == b'\n' {
+            return Ok(cur);
+        }
+    }
+    Ok(file_len)
+}
+
+/// Read one newline-aligned byte range of an input file, sort its lines, and write them to a
+/// chunk file, reusing the same line-range-based sort as the sequential path
+fn sort_byte_range_to_chunk(
+    args: &Args,
+    config: &SortConfig,
+    path: &PathBuf,
+    start: u64,
+    end: u64,
+) -> io::Result<PathBuf> {
+    let mut file = File::open(path)?;
+    file.seek(SeekFrom::Start(start))?;
+
+    let mut data = vec![0u8; (end - start) as usize];
+    file.read_exact(&mut data)?;
+
+    let mut ranges = collect_line_ranges(&data);
+    write_sorted_chunk(&args.temp_dir, &data, &mut ranges, config, args.compress)
+}
+
+/// Find the `(start, end)` byte range of every line (including its trailing `\n`, if present) in `data`
+fn collect_line_ranges(data: &[u8]) -> Vec<LineRange> {
+    let mut ranges = Vec::new();
+    let mut start = 0;
+    for (i, &b) in data.iter().enumerate() {
+        if b == b'\n' {
+            ranges.push((start, i + 1));
+            start = i + 1;
+        }
+    }
+    if start < data.len() {
+        ranges.push((start, data.len()));
+    }
+    ranges
+}
+
+/// Sort line ranges per `config`'s key comparator and write them, in order, to a tempfile.
+/// When `compress_level` is set the chunk is written as a zstd stream; since adjacent sorted
+/// lines tend to share long common prefixes this compresses very well.
+fn write_sorted_chunk(
+    temp_dir: &PathBuf,
+    data: &[u8],
+    ranges: &mut Vec<LineRange>,
+    config: &SortConfig,
+    compress_level: Option<i32>,
+) -> io::Result<PathBuf> {
+    ranges.par_sort_unstable_by(|&(a_start, a_end), &(b_start, b_end)| {
+        config.compare(&data[a_start..a_end], &data[b_start..b_end])
+    });
 
-    for line in buffer.drain(..) {
-        file.write_all(&line)?;
+    let file_path = temp_dir.join(format!("chunk_{}.tmp", uuid::Uuid::new_v4()));
+    let raw = BufWriter::new(File::create(&file_path)?);
+
+    // `zstd::Encoder::auto_finish()` would swallow any error from writing the closing frame on
+    // drop, so finish it explicitly here and propagate whatever it returns, including the flush
+    // of the underlying `BufWriter`. A line without a trailing `\n` (the end of the last input)
+    // gets one, so it is not glued onto whichever line is sorted after it.
+    match compress_level {
+        Some(level) => {
+            let mut encoder = zstd::Encoder::new(raw, level)?;
+            for &(start, end) in ranges.iter() {
+                encoder.write_all(&data[start..end])?;
+                if data[end - 1] != b'\n' {
+                    encoder.write_all(b"\n")?;
+                }
+            }
+            encoder.finish()?.flush()?;
+        }
+        None => {
+            let mut file = raw;
+            for &(start, end) in ranges.iter() {
+                file.write_all(&data[start..end])?;
+                if data[end - 1] != b'\n' {
+                    file.write_all(b"\n")?;
+                }
+            }
+            file.flush()?;
+        }
     }
 
-    file.flush()?;
     Ok(file_path)
 }
 
-/// Merge sorted chunk files into final deduplicated output
-fn merge_chunks(chunk_files: Vec<PathBuf>, output: &PathBuf) -> io::Result<()> {
-    let mut readers: Vec<_> = chunk_files
-        .iter()
-        .map(|p| BufReader::new(File::open(p).unwrap()))
-        .collect();
+/// A block of chunk-file bytes in flight from a reader thread to the merge thread
+struct Block {
+    data: Arc<Vec<u8>>,
+    len: usize,
+}
 
-    let mut heap = BinaryHeap::<HeapItem>::new();
-    let mut buffers: Vec<Vec<u8>> = vec![Vec::new(); readers.len()];
+/// Per-chunk-file merge state: the lines ready from the current block plus the channels
+/// used to pull the next block and hand back an exhausted buffer for recycling
+struct ChunkReader {
+    data_rx: mpsc::Receiver<Block>,
+    return_tx: mpsc::SyncSender<Vec<u8>>,
+    block: Arc<Vec<u8>>,
+    ranges: Vec<LineRange>,
+    next: usize,
+}
 
+impl ChunkReader {
+    /// Return the next line's range, pulling (and recycling) blocks from the reader thread
+    /// as the current one is exhausted. Returns `None` once the chunk file is fully consumed.
+    fn advance(&mut self) -> Option<LineRange> {
+        loop {
+            if self.next < self.ranges.len() {
+                let range = self.ranges[self.next];
+                self.next += 1;
+                return Some(range);
+            }
+
+            let old_block = std::mem::replace(&mut self.block, Arc::new(Vec::new()));
+            if let Ok(buf) = Arc::try_unwrap(old_block) {
+                let _ = self.return_tx.send(buf);
+            }
+
+            let block = self.data_rx.recv().ok()?;
+            self.ranges = collect_line_ranges(&block.data[..block.len]);
+            self.block = block.data;
+            self.next = 0;
+        }
+    }
+}
+
+/// Reader thread body: stream a chunk file in line-aligned blocks of `block_size` bytes (grown
+/// when a single line does not fit), sending each
+/// filled buffer to the merge thread and recycling buffers handed back over `return_rx`
+fn read_chunk_blocks(
+    path: &PathBuf,
+    block_size: usize,
+    compressed: bool,
+    data_tx: &mpsc::SyncSender<Block>,
+    return_rx: &mpsc::Receiver<Vec<u8>>,
+) -> io::Result<()> {
+    let mut file: Box<dyn Read> = if compressed {
+        Box::new(zstd::Decoder::new(File::open(path)?)?)
+    } else {
+        Box::new(File::open(path)?)
+    };
+    let mut block = vec![0u8; block_size];
+    let mut filled = 0;
+
+    loop {
+        let n = file.read(&mut block[filled..])?;
+        let end = filled + n;
+
+        if n == 0 {
+            if end > 0 {
+                let _ = data_tx.send(Block { data: Arc::new(block), len: end });
+            }
+            return Ok(());
+        }
+
+        let split_at = match block[..end].iter().rposition(|&b| b == b'\n') {
+            Some(pos) => pos + 1,
+            None => {
+                if end == block.len() {
+                    block.resize(block.len() * 2, 0);
+                }
+                filled = end;
+                continue;
+            }
+        };
+
+        // Reuse a buffer the merge thread has finished with, if one is waiting, instead of
+        // allocating a fresh one for every block.
+        let mut next_block = return_rx.try_recv().unwrap_or_default();
+        if next_block.len() < block.len() {
+            next_block.resize(block.len(), 0);
+        }
+        next_block[..end - split_at].copy_from_slice(&block[split_at..end]);
+
+        let sent_len = split_at;
+        let data = Arc::new(std::mem::replace(&mut block, next_block));
+        if data_tx.send(Block { data, len: sent_len }).is_err() {
+            return Ok(());
+        }
+
+        filled = end - split_at;
+    }
+}
+
+/// Merge sorted chunk files into final deduplicated output. Each chunk file is read by its own
+/// thread in large blocks, overlapping disk I/O with the merge comparisons, and buffers flow
+/// back to their reader thread for reuse once the merge thread is done with them.
+fn merge_chunks(chunk_files: Vec<PathBuf>, args: &Args, config: &Arc<SortConfig>) -> io::Result<()> {
+    const PIPELINE_DEPTH: usize = 2;
+
+    let mut readers = Vec::with_capacity(chunk_files.len());
+    let mut join_handles = Vec::with_capacity(chunk_files.len());
+
+    for path in &chunk_files {
+        let (data_tx, data_rx) = mpsc::sync_channel::<Block>(PIPELINE_DEPTH);
+        let (return_tx, return_rx) = mpsc::sync_channel::<Vec<u8>>(PIPELINE_DEPTH);
+        let block_size = args.chunk_size;
+        let compressed = args.compress.is_some();
+        let path = path.clone();
+
+        join_handles
+            .push(thread::spawn(move || read_chunk_blocks(&path, block_size, compressed, &data_tx, &return_rx)));
+
+        readers.push(ChunkReader {
+            data_rx,
+            return_tx,
+            block: Arc::new(Vec::new()),
+            ranges: Vec::new(),
+            next: 0,
+        });
+    }
+
+    let mut heap = BinaryHeap::<HeapItem>::new();
     for (i, reader) in readers.iter_mut().enumerate() {
-        if reader.read_until(b'\n', &mut buffers[i]).unwrap() > 0 {
-            heap.push(HeapItem {
-                line: buffers[i].clone(),
-                index: i,
-            });
-            buffers[i].clear();
+        if let Some(range) = reader.advance() {
+            heap.push(HeapItem { block: reader.block.clone(), range, index: i, config: config.clone() });
         }
     }
 
-    let mut out = BufWriter::new(File::create(output)?);
+    let mut out = BufWriter::new(File::create(&args.output)?);
     let mut last_written: Option<Line> = None;
 
     let pb = ProgressBar::new_spinner();
     pb.set_style(ProgressStyle::with_template("{spinner} Merging lines... {pos} processed").unwrap());
 
-    while let Some(HeapItem { line, index }) = heap.pop() {
-        if last_written.as_ref().map_or(true, |prev| *prev != line) {
-            out.write_all(&line)?;
-            last_written = Some(line.clone());
+    while let Some(item) = heap.pop() {
+        let index = item.index;
+        let is_new = match &last_written {
+            Some(prev) => config.compare(prev, item.as_bytes()) != Ordering::Equal,
+            None => true,
+        };
+        if is_new {
+            out.write_all(item.as_bytes())?;
+            last_written = Some(item.as_bytes().to_vec());
         }
         pb.inc(1);
 
-        if readers[index].read_until(b'\n', &mut buffers[index]).unwrap() > 0 {
-            heap.push(HeapItem {
-                line: buffers[index].clone(),
-                index,
-            });
-            buffers[index].clear();
+        // Drop `item` (and its Arc clone of the current block) before advancing, so that once
+        // the block is fully consumed `ChunkReader::advance` is the sole remaining owner and can
+        // actually reclaim its buffer instead of always falling through to a fresh allocation.
+        drop(item);
+
+        let reader = &mut readers[index];
+        if let Some(range) = reader.advance() {
+            heap.push(HeapItem { block: reader.block.clone(), range, index, config: config.clone() });
         }
     }
 
     pb.finish_with_message("✅ Merge complete");
 
+    // A reader thread exiting early with an I/O error looks the same to `ChunkReader::advance`
+    // as that chunk file running out normally (its `data_tx` just gets dropped), so the only way
+    // to catch a truncated merge is to check what each thread actually returned.
+    let mut reader_error = None;
+    for handle in join_handles {
+        let result = handle
+            .join()
+            .unwrap_or_else(|_| Err(io::Error::new(io::ErrorKind::Other, "chunk reader thread panicked")));
+        if let Err(e) = result {
+            reader_error.get_or_insert(e);
+        }
+    }
+
     for f in chunk_files {
         let _ = fs::remove_file(f);
     }
 
     out.flush()?;
+    if let Some(e) = reader_error {
+        return Err(e);
+    }
     Ok(())
 }
 
-#[derive(Eq, Clone)]
+#[derive(Clone)]
 struct HeapItem {
-    line: Line,
+    block: Arc<Vec<u8>>,
+    range: LineRange,
     index: usize,
+    config: Arc<SortConfig>,
+}
+
+impl HeapItem {
+    fn as_bytes(&self) -> &[u8] {
+        &self.block[self.range.0..self.range.1]
+    }
 }
 
+impl Eq for HeapItem {}
 impl Ord for HeapItem {
     fn cmp(&self, other: &Self) -> Ordering {
-        other.line.cmp(&self.line)
+        self.config.compare(other.as_bytes(), self.as_bytes())
     }
 }
 impl PartialOrd for HeapItem {
@@ -162,7 +745,68 @@ impl PartialOrd for HeapItem {
 }
 impl PartialEq for HeapItem {
     fn eq(&self, other: &Self) -> bool {
-        self.line == other.line
+        self.config.compare(self.as_bytes(), other.as_bytes()) == Ordering::Equal
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strip_line_ending_lf() {
+        assert_eq!(strip_line_ending(b"abc\n"), b"abc");
+    }
+
+    #[test]
+    fn strip_line_ending_crlf() {
+        assert_eq!(strip_line_ending(b"abc\r\n"), b"abc");
+    }
+
+    #[test]
+    fn strip_line_ending_no_terminator() {
+        assert_eq!(strip_line_ending(b"abc"), b"abc");
+    }
+
+    #[test]
+    fn parse_sort_key_rejects_zero_column() {
+        assert!(parse_sort_key("0").is_err());
+    }
+
+    #[test]
+    fn parse_sort_key_rejects_end_before_start() {
+        assert!(parse_sort_key("3-1").is_err());
+    }
+
+    #[test]
+    fn parse_sort_key_rejects_unknown_modifier() {
+        assert!(parse_sort_key("1:x").is_err());
+    }
+
+    #[test]
+    fn parse_sort_key_accepts_range_with_modifiers() {
+        let spec = parse_sort_key("2-4:nr").unwrap();
+        assert_eq!(spec.start, 2);
+        assert_eq!(spec.end, 4);
+        assert!(spec.numeric);
+        assert!(spec.reverse);
+        assert!(!spec.fold_case);
+    }
+
+    #[test]
+    fn extract_key_start_past_last_field_is_empty() {
+        let line = b"a\tb\tc";
+        let fields = field_ranges(line, b'\t');
+        let key = SortKeySpec { start: 10, end: 10, numeric: false, reverse: false, fold_case: false };
+        assert_eq!(extract_key(line, &fields, &key), b"");
+    }
+
+    #[test]
+    fn extract_key_end_past_last_field_clamps_to_last() {
+        let line = b"a\tb\tc";
+        let fields = field_ranges(line, b'\t');
+        let key = SortKeySpec { start: 2, end: 10, numeric: false, reverse: false, fold_case: false };
+        assert_eq!(extract_key(line, &fields, &key), b"b\tc");
     }
 }
 